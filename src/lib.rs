@@ -0,0 +1,575 @@
+//! Core Mandelbrot-and-friends rendering: escape-time fractals and the
+//! Buddhabrot, as pure math over pixel buffers. Nothing in this crate
+//! touches the filesystem, so the same code can back the CLI binary (see
+//! `main.rs`) and, behind the `wasm` feature, an in-browser canvas explorer
+//! (see the `wasm` module below).
+
+use std::str::FromStr;
+
+use num::Complex;
+use rand::Rng;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// Which escape-time fractal to render.
+///
+/// All variants share the same escape test and iteration limit; only the
+/// per-iteration transform in `step` differs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FractalKind {
+    /// The classic `z = z*z + c`.
+    Mandelbrot,
+    /// The "Multibrot" with cubic iteration, `z = z*z*z + c`.
+    Multibrot3,
+    /// `z = z*z + c`, but each component of `z` is folded into the positive
+    /// quadrant (`abs`) before squaring.
+    BurningShip,
+}
+
+impl FromStr for FractalKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mandelbrot" => Ok(FractalKind::Mandelbrot),
+            "mandelbrot3" => Ok(FractalKind::Multibrot3),
+            "burning_ship" => Ok(FractalKind::BurningShip),
+            _ => Err(format!(
+                "unknown fractal kind '{}' (expected mandelbrot, mandelbrot3, or burning_ship)",
+                s
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_fractal_kind_from_str() {
+    assert_eq!(FractalKind::from_str("mandelbrot"), Ok(FractalKind::Mandelbrot));
+    assert_eq!(FractalKind::from_str("mandelbrot3"), Ok(FractalKind::Multibrot3));
+    assert_eq!(FractalKind::from_str("burning_ship"), Ok(FractalKind::BurningShip));
+    assert!(FractalKind::from_str("julia").is_err());
+}
+
+/// Which rendering subsystem to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Render `FractalKind`'s escape-time image.
+    EscapeTime,
+    /// Render the Buddhabrot, a histogram of escaping orbits.
+    Buddhabrot,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "escape_time" => Ok(Mode::EscapeTime),
+            "buddhabrot" => Ok(Mode::Buddhabrot),
+            _ => Err(format!(
+                "unknown mode '{}' (expected escape_time or buddhabrot)",
+                s
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_mode_from_str() {
+    assert_eq!(Mode::from_str("escape_time"), Ok(Mode::EscapeTime));
+    assert_eq!(Mode::from_str("buddhabrot"), Ok(Mode::Buddhabrot));
+    assert!(Mode::from_str("orbit").is_err());
+}
+
+/// A color gradient used to map a smooth escape count to an RGB pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    /// A plain grayscale ramp.
+    Grayscale,
+    /// A fully saturated HSV gradient, cycling through the whole hue circle.
+    Fire,
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grayscale" => Ok(Palette::Grayscale),
+            "fire" => Ok(Palette::Fire),
+            _ => Err(format!(
+                "unknown palette '{}' (expected grayscale or fire)",
+                s
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_palette_from_str() {
+    assert_eq!(Palette::from_str("grayscale"), Ok(Palette::Grayscale));
+    assert_eq!(Palette::from_str("fire"), Ok(Palette::Fire));
+    assert!(Palette::from_str("rainbow").is_err());
+}
+
+/// Apply one iteration step of `kind` to `z`, given the point `c`.
+fn step(z: Complex<f64>, c: Complex<f64>, kind: FractalKind) -> Complex<f64> {
+    match kind {
+        FractalKind::Mandelbrot => z * z + c,
+        FractalKind::Multibrot3 => z * z * z + c,
+        FractalKind::BurningShip => {
+            let folded = Complex {
+                re: z.re.abs(),
+                im: z.im.abs(),
+            };
+            folded * folded + c
+        }
+    }
+}
+
+#[test]
+fn test_step() {
+    let z = Complex { re: 1.0, im: -2.0 };
+    let c = Complex { re: 0.5, im: 0.5 };
+
+    assert_eq!(step(z, c, FractalKind::Mandelbrot), z * z + c);
+    assert_eq!(step(z, c, FractalKind::Multibrot3), z * z * z + c);
+
+    let folded = Complex { re: 1.0, im: 2.0 };
+    assert_eq!(
+        step(z, c, FractalKind::BurningShip),
+        folded * folded + c
+    );
+}
+
+/// Try to determine if `c` is in the given fractal's set, using at most
+/// `limit` iterations to decide.
+///
+/// `radius` is the escape radius: once `|z|` exceeds it, `c` is considered to
+/// have escaped. The default of 2.0 is the smallest value that's provably
+/// correct for the Mandelbrot set, but a larger radius (4-8) gives smoother
+/// continuous coloring, since the orbit travels further before the test
+/// trips.
+///
+/// If `c` is not a member, return `Some((i, z))`, where `i` is the number of
+/// iterations it took for `c` to leave the circle of the given radius
+/// centered on the origin, and `z` is the escaped value (needed by the
+/// caller to compute a smooth, continuous iteration count). If `c` seems to
+/// be a member (more precisely, if we reached the iteration limit without
+/// being able to prove that `c` is not a member), return `None`.
+pub fn escape_time(
+    c: Complex<f64>,
+    limit: usize,
+    kind: FractalKind,
+    radius: f64,
+) -> Option<(usize, Complex<f64>)> {
+    let mut z = Complex { re: 0.0, im: 0.0 };
+    let radius_sqr = radius * radius;
+    for i in 0..limit {
+        if z.norm_sqr() > radius_sqr {
+            return Some((i, z));
+        }
+        z = step(z, c, kind);
+    }
+
+    None
+}
+
+/// The polynomial degree of `kind`'s iteration step (2 for `z*z + c`, 3 for
+/// `z*z*z + c`), i.e. the base the smooth-coloring logarithm must use.
+fn degree(kind: FractalKind) -> f64 {
+    match kind {
+        FractalKind::Mandelbrot => 2.0,
+        FractalKind::Multibrot3 => 3.0,
+        FractalKind::BurningShip => 2.0,
+    }
+}
+
+/// Compute the fractional ("smooth") iteration count for a point that
+/// escaped after `i` integer iterations with final value `z`, under a
+/// fractal of the given polynomial `degree`.
+///
+/// This removes the banding that comes from coloring purely by the integer
+/// iteration count. The `ln(degree)` denominator matters: it's what scales
+/// the fractional term into `[0, 1)` per iteration, so using the wrong
+/// degree (e.g. 2 for a cubic fractal) reintroduces banding.
+fn smoothed_count(i: usize, z: Complex<f64>, degree: f64) -> f64 {
+    let log_zn = z.norm_sqr().ln() / 2.0;
+    let nu = log_zn.ln() / degree.ln();
+    i as f64 + 1.0 - nu
+}
+
+/// Map a smooth iteration count `mu` to an RGB triple using `palette`.
+///
+/// `mu` is `None` for interior points, which are always rendered black.
+/// `limit` is the iteration limit `mu` was computed against; the grayscale
+/// palette uses it to rescale `mu` into the `[0, 255]` color depth rather
+/// than wrapping it. The fire palette ignores it and wraps around the hue
+/// circle.
+fn color_at(mu: Option<f64>, palette: Palette, limit: usize) -> [u8; 3] {
+    let mu = match mu {
+        None => return [0, 0, 0],
+        Some(mu) => mu,
+    };
+
+    match palette {
+        Palette::Grayscale => {
+            let v = (mu / limit as f64 * 255.0).clamp(0.0, 255.0) as u8;
+            [v, v, v]
+        }
+        Palette::Fire => {
+            let hue = (mu * 6.0).rem_euclid(360.0);
+            hsv_to_rgb(hue, 1.0, 1.0)
+        }
+    }
+}
+
+/// Convert an HSV color (`h` in `[0, 360)`, `s` and `v` in `[0, 1]`) to RGB.
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [u8; 3] {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+
+    let (r, g, b) = match h as u32 {
+        0..=59 => (c, x, 0.0),
+        60..=119 => (x, c, 0.0),
+        120..=179 => (0.0, c, x),
+        180..=239 => (0.0, x, c),
+        240..=299 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    [
+        (((r + m) * 255.0).round()) as u8,
+        (((g + m) * 255.0).round()) as u8,
+        (((b + m) * 255.0).round()) as u8,
+    ]
+}
+
+/// Given the row and column of a pixel in the output image, return the
+/// corresponding point on the complex plane.
+///
+/// `bounds` is a pair giving the width and height of the image in pixels.
+/// `pixel` is a (column, row) pair indicating a particular pixel in that image.
+/// The `upper_left` and `lower_right` parameters are points on the complex plane
+/// designating the area our image covers.
+pub fn pixel_to_point(
+    bounds: (usize, usize),
+    pixel: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+) -> Complex<f64> {
+    // We treat re as x and im as y
+    let (width, height) = (
+        lower_right.re - upper_left.re,
+        upper_left.im - lower_right.im,
+    );
+
+    let pixel_x = pixel.0 as f64;
+    let pixel_y = pixel.1 as f64;
+    let bounds_x = bounds.0 as f64;
+    let bounds_y = bounds.1 as f64;
+
+    Complex {
+        re: upper_left.re + pixel_x * width / bounds_x,
+        im: upper_left.im - pixel_y * height / bounds_y,
+        // We subtract because pixel y increases as we go down,
+        // but the imaginary component increases as we go up
+    }
+}
+
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(
+        pixel_to_point(
+            (100, 200),
+            (25, 175),
+            Complex { re: -1.0, im: 1.0 },
+            Complex { re: 1.0, im: -1.0 },
+        ),
+        Complex {
+            re: -0.5,
+            im: -0.75,
+        }
+    );
+}
+
+/// The inverse of `pixel_to_point`: given a point on the complex plane,
+/// return the pixel it falls into, or `None` if it falls outside `bounds`.
+pub fn point_to_pixel(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    point: Complex<f64>,
+) -> Option<(usize, usize)> {
+    let (width, height) = (
+        lower_right.re - upper_left.re,
+        upper_left.im - lower_right.im,
+    );
+
+    let column = ((point.re - upper_left.re) / width * bounds.0 as f64) as isize;
+    let row = ((upper_left.im - point.im) / height * bounds.1 as f64) as isize;
+
+    if column < 0 || row < 0 || column as usize >= bounds.0 || row as usize >= bounds.1 {
+        None
+    } else {
+        Some((column as usize, row as usize))
+    }
+}
+
+#[test]
+fn test_point_to_pixel_round_trip() {
+    let bounds = (100, 200);
+    let upper_left = Complex { re: -1.0, im: 1.0 };
+    let lower_right = Complex { re: 1.0, im: -1.0 };
+
+    let pixel = (25, 175);
+    let point = pixel_to_point(bounds, pixel, upper_left, lower_right);
+    assert_eq!(
+        point_to_pixel(bounds, upper_left, lower_right, point),
+        Some(pixel)
+    );
+
+    // A point outside the view maps to no pixel.
+    let outside = Complex { re: 5.0, im: 5.0 };
+    assert_eq!(point_to_pixel(bounds, upper_left, lower_right, outside), None);
+}
+
+/// Fill `pixels` (an RGB buffer of `bounds.0 * bounds.1 * 3` bytes) with the
+/// escape-time render of the rectangle between `upper_left` and
+/// `lower_right`.
+#[allow(clippy::too_many_arguments)]
+pub fn render(
+    pixels: &mut [u8],
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    fractal: FractalKind,
+    palette: Palette,
+    limit: usize,
+    radius: f64,
+) {
+    assert_eq!(pixels.len(), bounds.0 * bounds.1 * 3);
+
+    for row in 0..bounds.1 {
+        for column in 0..bounds.0 {
+            let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
+            let mu =
+                escape_time(point, limit, fractal, radius)
+                    .map(|(i, z)| smoothed_count(i, z, degree(fractal)));
+            let rgb = color_at(mu, palette, limit);
+            let pixel_index = (row * bounds.0 + column) * 3;
+            pixels[pixel_index..pixel_index + 3].copy_from_slice(&rgb);
+        }
+    }
+}
+
+/// Render the escape-time image (Mandelbrot and friends), parallelizing one
+/// row per Rayon task, and return the resulting RGB pixel buffer. This is
+/// the core entry point shared by the CLI and the `wasm` bindings below.
+pub fn render_image(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    fractal: FractalKind,
+    palette: Palette,
+    limit: usize,
+    radius: f64,
+) -> Vec<u8> {
+    let mut pixels = vec![0; bounds.0 * bounds.1 * 3];
+
+    {
+        let bands: Vec<(usize, &mut [u8])> =
+            pixels.chunks_mut(bounds.0 * 3).enumerate().collect();
+
+        bands.into_par_iter().for_each(|(i, band)| {
+            let top = i;
+            let width = bounds.0;
+            let height = 1;
+            let band_bounds = (width, height); // Just one row
+            let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
+            let band_lower_right =
+                pixel_to_point(bounds, (width, top + height), upper_left, lower_right);
+            render(
+                band,
+                band_bounds,
+                band_upper_left,
+                band_lower_right,
+                fractal,
+                palette,
+                limit,
+                radius,
+            );
+        });
+    }
+
+    pixels
+}
+
+/// Number of sample batches to split across Rayon threads when computing the
+/// Buddhabrot histogram; each batch accumulates into its own local histogram,
+/// which are then summed together.
+const BUDDHABROT_BATCHES: usize = 64;
+
+/// Build an ascending `Range` from two bounds given in either order, so
+/// `rng.gen_range` doesn't panic when the bounds come in reverse (as
+/// `-radius, radius` does for a negative radius).
+fn ordered_range(a: f64, b: f64) -> std::ops::Range<f64> {
+    if a <= b {
+        a..b
+    } else {
+        b..a
+    }
+}
+
+/// Sample `samples` random points `c` from the `[-radius, radius]` square
+/// (a superset of the view, since no point outside the escape radius could
+/// ever produce an orbit that visits it) rather than from the
+/// `upper_left`/`lower_right` view rectangle itself. Restricting sampling to
+/// the view would drop orbits that originate outside it but still pass
+/// through it on their way to escaping, which is most of the Buddhabrot's
+/// characteristic density. Each orbit is traced under `z = z*z + c` for up
+/// to `limit` iterations, and for every orbit that escapes, a hit is
+/// recorded in `bounds` for each intermediate `z` that lands on a pixel.
+/// Orbits that never escape are discarded, as they never reach the
+/// Mandelbrot set's boundary.
+pub fn buddhabrot(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    samples: usize,
+    limit: usize,
+    radius: f64,
+) -> Vec<u32> {
+    let radius_sqr = radius * radius;
+    let re_range = ordered_range(-radius, radius);
+    let im_range = ordered_range(-radius, radius);
+
+    (0..BUDDHABROT_BATCHES)
+        .into_par_iter()
+        .map(|batch| {
+            // Spread the remainder over the first batches so the total
+            // number of samples is exactly `samples`.
+            let samples_per_batch = samples / BUDDHABROT_BATCHES
+                + usize::from(batch < samples % BUDDHABROT_BATCHES);
+            let mut local = vec![0u32; bounds.0 * bounds.1];
+            let mut rng = rand::thread_rng();
+            let mut orbit = Vec::with_capacity(limit);
+
+            for _ in 0..samples_per_batch {
+                let c = Complex {
+                    re: rng.gen_range(re_range.clone()),
+                    im: rng.gen_range(im_range.clone()),
+                };
+
+                let mut z = Complex { re: 0.0, im: 0.0 };
+                orbit.clear();
+                let mut escaped = false;
+                for _ in 0..limit {
+                    if z.norm_sqr() > radius_sqr {
+                        escaped = true;
+                        break;
+                    }
+                    orbit.push(z);
+                    z = z * z + c;
+                }
+
+                if escaped {
+                    // Skip the starting `z = 0`, which is not an intermediate
+                    // value of the orbit.
+                    for &point in orbit.iter().skip(1) {
+                        if let Some((column, row)) =
+                            point_to_pixel(bounds, upper_left, lower_right, point)
+                        {
+                            local[row * bounds.0 + column] += 1;
+                        }
+                    }
+                }
+            }
+
+            local
+        })
+        .reduce(
+            || vec![0u32; bounds.0 * bounds.1],
+            |mut a, b| {
+                for (hit, other) in a.iter_mut().zip(b.iter()) {
+                    *hit += other;
+                }
+                a
+            },
+        )
+}
+
+/// Render the Buddhabrot image by sampling random points, tracing their
+/// orbits, and accumulating a visit histogram, then normalize it to a
+/// grayscale RGB pixel buffer.
+pub fn render_buddhabrot_image(
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    samples: usize,
+    limit: usize,
+    radius: f64,
+) -> Vec<u8> {
+    let histogram = buddhabrot(bounds, upper_left, lower_right, samples, limit, radius);
+    let max = histogram.iter().copied().max().unwrap_or(0).max(1);
+
+    let mut pixels = vec![0u8; bounds.0 * bounds.1 * 3];
+    for (i, &hits) in histogram.iter().enumerate() {
+        let v = ((hits as f64 / max as f64) * 255.0).round() as u8;
+        let pixel_index = i * 3;
+        pixels[pixel_index] = v;
+        pixels[pixel_index + 1] = v;
+        pixels[pixel_index + 2] = v;
+    }
+
+    pixels
+}
+
+/// A thin, browser-facing entry point: the same math as above, exposed
+/// through `wasm-bindgen` so an HTML canvas can drive it directly. Only
+/// compiled in when the `wasm` feature is enabled.
+#[cfg(feature = "wasm")]
+pub mod wasm {
+    use super::*;
+    use wasm_bindgen::prelude::*;
+
+    /// Render the Mandelbrot set into an RGBA buffer suitable for drawing
+    /// straight into a canvas `ImageData`.
+    #[wasm_bindgen]
+    pub fn render_rgba(
+        width: usize,
+        height: usize,
+        upper_left_re: f64,
+        upper_left_im: f64,
+        lower_right_re: f64,
+        lower_right_im: f64,
+        limit: usize,
+    ) -> Vec<u8> {
+        let bounds = (width, height);
+        let upper_left = Complex {
+            re: upper_left_re,
+            im: upper_left_im,
+        };
+        let lower_right = Complex {
+            re: lower_right_re,
+            im: lower_right_im,
+        };
+
+        let rgb = render_image(
+            bounds,
+            upper_left,
+            lower_right,
+            FractalKind::Mandelbrot,
+            Palette::Fire,
+            limit,
+            2.0,
+        );
+
+        let mut rgba = Vec::with_capacity(bounds.0 * bounds.1 * 4);
+        for pixel in rgb.chunks_exact(3) {
+            rgba.extend_from_slice(pixel);
+            rgba.push(255);
+        }
+        rgba
+    }
+}
@@ -1,19 +1,28 @@
 use std::env;
 use std::fs::File;
+use std::io::Write;
 use std::str::FromStr;
 
 use image::png::PNGEncoder;
 use image::ColorType;
 use num::Complex;
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use mandelbrot_generator::{render_buddhabrot_image, render_image, FractalKind, Mode, Palette};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 5 {
-        eprintln!("Usage: {} FILE PIXELS UPPERLEFT LOWERRIGHT", args[0]);
+    if args.len() < 5 {
+        eprintln!(
+            "Usage: {} FILE PIXELS UPPERLEFT LOWERRIGHT [--fractal KIND] [--palette PALETTE] [--mode MODE] [--samples N] [--limit N] [--radius R] [--format FORMAT]",
+            args[0]
+        );
         eprintln!(
-            "Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20",
+            "Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20 --fractal mandelbrot --palette fire",
+            args[0]
+        );
+        eprintln!(
+            "Example: {} buddha.png 1000x750 -2,1.5 1,-1.5 --mode buddhabrot --samples 1000000 --limit 200",
             args[0]
         );
         std::process::exit(1);
@@ -23,49 +32,133 @@ fn main() {
     let upper_left = parse_complex(&args[3]).expect("Error parsing upper left corner point.");
     let lower_right = parse_complex(&args[4]).expect("Error parsing lower right corner point.");
 
-    let total_pixels = bounds.0 * bounds.1;
-    let mut pixels = vec![0; total_pixels];
-
-    // Now we let Rayon take care of the parallelism
-    // let threads = num_cpus::get();
-    // println!("Running on {threads}");
-    {
-        // let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * bounds.0).collect();
-        let bands: Vec<(usize, &mut [u8])> = pixels.chunks_mut(bounds.0).enumerate().collect();
-
-        bands.into_par_iter().for_each(|(i, band)| {
-            let top = i;
-            let width = bounds.0;
-            let height = 1;
-            let band_bounds = (width, height); // Just one row
-            let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-            let band_lower_right =
-                pixel_to_point(bounds, (width, top + height), upper_left, lower_right);
-            render(band, band_bounds, band_upper_left, band_lower_right);
-        });
+    let mut fractal = FractalKind::Mandelbrot;
+    let mut palette = Palette::Grayscale;
+    let mut mode = Mode::EscapeTime;
+    let mut samples: usize = 1_000_000;
+    let mut limit: usize = 100;
+    let mut radius: f64 = 2.0;
+    let mut format = None;
+    let mut i = 5;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--fractal" => {
+                i += 1;
+                fractal = FractalKind::from_str(next_arg(&args, i)).expect("Error parsing fractal kind.");
+            }
+            "--palette" => {
+                i += 1;
+                palette = Palette::from_str(next_arg(&args, i)).expect("Error parsing palette.");
+            }
+            "--mode" => {
+                i += 1;
+                mode = Mode::from_str(next_arg(&args, i)).expect("Error parsing mode.");
+            }
+            "--samples" => {
+                i += 1;
+                samples = next_arg(&args, i).parse().expect("Error parsing sample count.");
+            }
+            "--limit" => {
+                i += 1;
+                limit = next_arg(&args, i).parse().expect("Error parsing iteration limit.");
+            }
+            "--radius" => {
+                i += 1;
+                radius = next_arg(&args, i).parse().expect("Error parsing escape radius.");
+            }
+            "--format" => {
+                i += 1;
+                format = Some(
+                    OutputFormat::from_str(next_arg(&args, i)).expect("Error parsing output format."),
+                );
+            }
+            other => {
+                eprintln!("Unknown argument: {}", other);
+                std::process::exit(1);
+            }
+        }
+        i += 1;
     }
 
-    write_image(&args[1], &pixels, bounds).expect("Error writing PNG file.");
+    let pixels = match mode {
+        Mode::EscapeTime => {
+            render_image(bounds, upper_left, lower_right, fractal, palette, limit, radius)
+        }
+        Mode::Buddhabrot => {
+            render_buddhabrot_image(bounds, upper_left, lower_right, samples, limit, radius)
+        }
+    };
+
+    let format = format
+        .or_else(|| OutputFormat::from_extension(&args[1]))
+        .expect("Could not infer output format from file extension; pass --format explicitly.");
+
+    write_image(&args[1], &pixels, bounds, format).expect("Error writing output file.");
 }
 
-/// Try to determine if `c` is in the Mandelbrot set, using at most `limit`
-/// iterations to decide.
-///
-/// If `c` is not a member, return `Some(i)`, where `i` is the number of
-/// iterations it took for `c` to leave the circle of radius 2 centered on the
-/// origin. If `c` seems to be a member (more precisely, if we reached the
-/// iteration limit without being able to prove that `c` is not a member),
-/// return `None`.
-fn escape_time(c: Complex<f64>, limit: usize) -> Option<usize> {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-    for i in 0..limit {
-        if z.norm_sqr() > 4.0 {
-            return Some(i);
+/// Fetch the flag value at `args[i]`, exiting with a clear error instead of
+/// panicking on an out-of-bounds index if a flag was given with no value.
+fn next_arg(args: &[String], i: usize) -> &str {
+    args.get(i).unwrap_or_else(|| {
+        eprintln!("Missing value for argument {}", args[i - 1]);
+        std::process::exit(1);
+    })
+}
+
+/// The output file format, chosen either from an explicit `--format` flag or
+/// inferred from the output file's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// PNG, via the `image` crate.
+    Png,
+    /// Binary PGM (`P5`): a grayscale ramp of the RGB buffer's luma.
+    Pgm,
+    /// Binary PPM (`P6`): the RGB buffer written out directly.
+    Ppm,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(OutputFormat::Png),
+            "pgm" => Ok(OutputFormat::Pgm),
+            "ppm" => Ok(OutputFormat::Ppm),
+            _ => Err(format!("unknown format '{}' (expected png, pgm, or ppm)", s)),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Infer the format from a filename's extension, or `None` if it is not
+    /// one we recognize.
+    fn from_extension(filename: &str) -> Option<Self> {
+        let extension = filename.rsplit('.').next()?;
+        match extension {
+            "png" => Some(OutputFormat::Png),
+            "pgm" => Some(OutputFormat::Pgm),
+            "ppm" => Some(OutputFormat::Ppm),
+            _ => None,
         }
-        z = z * z + c;
     }
+}
+
+#[test]
+fn test_output_format_from_str() {
+    assert_eq!(OutputFormat::from_str("png"), Ok(OutputFormat::Png));
+    assert_eq!(OutputFormat::from_str("pgm"), Ok(OutputFormat::Pgm));
+    assert_eq!(OutputFormat::from_str("ppm"), Ok(OutputFormat::Ppm));
+    assert!(OutputFormat::from_str("bmp").is_err());
+}
 
-    None
+#[test]
+fn test_output_format_from_extension() {
+    assert_eq!(OutputFormat::from_extension("mandel.png"), Some(OutputFormat::Png));
+    assert_eq!(OutputFormat::from_extension("mandel.pgm"), Some(OutputFormat::Pgm));
+    assert_eq!(OutputFormat::from_extension("mandel.ppm"), Some(OutputFormat::Ppm));
+    assert_eq!(OutputFormat::from_extension("mandel.jpg"), None);
+    assert_eq!(OutputFormat::from_extension("mandel"), None);
 }
 
 /// Parse the string `s` as a coordinate pair, like `"400x600"` or `"1.0,0.5`.
@@ -129,85 +222,59 @@ fn parse_complex(s: &str) -> Option<Complex<f64>> {
     })
 }
 
-/// Given the row and column of a pixel in the output image, return the
-/// corresponding point on the complex plane.
-///
-/// `bounds` is a pair giving the width and height of the image in pixels.
-/// `pixel` is a (column, row) pair indicating a particular pixel in that image.
-/// The `upper_left` and `lower_right` parameters are points on the complex plane
-/// designating the area our image covers.
-fn pixel_to_point(
+/// Write the RGB buffer `pixels`, whose dimensions are given by `bounds`, to
+/// the file named `filename`, in the given `format`.
+fn write_image(
+    filename: &str,
+    pixels: &[u8],
     bounds: (usize, usize),
-    pixel: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) -> Complex<f64> {
-    // We treat re as x and im as y
-    let (width, height) = (
-        lower_right.re - upper_left.re,
-        upper_left.im - lower_right.im,
-    );
-
-    let pixel_x = pixel.0 as f64;
-    let pixel_y = pixel.1 as f64;
-    let bounds_x = bounds.0 as f64;
-    let bounds_y = bounds.1 as f64;
-
-    Complex {
-        re: upper_left.re + pixel_x * width / bounds_x,
-        im: upper_left.im - pixel_y * height / bounds_y,
-        // We subtract because pixel y increases as we go down,
-        // but the imaginary component increases as we go up
+    format: OutputFormat,
+) -> Result<(), std::io::Error> {
+    match format {
+        OutputFormat::Png => write_png(filename, pixels, bounds),
+        OutputFormat::Pgm => write_pgm(filename, pixels, bounds),
+        OutputFormat::Ppm => write_ppm(filename, pixels, bounds),
     }
 }
 
-#[test]
-fn test_pixel_to_point() {
-    assert_eq!(
-        pixel_to_point(
-            (100, 200),
-            (25, 175),
-            Complex { re: -1.0, im: 1.0 },
-            Complex { re: 1.0, im: -1.0 },
-        ),
-        Complex {
-            re: -0.5,
-            im: -0.75,
-        }
-    );
+fn write_png(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> Result<(), std::io::Error> {
+    let output = File::create(filename)?;
+
+    let encoder = PNGEncoder::new(output);
+    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, ColorType::RGB(8))?;
+
+    Ok(())
 }
 
-fn render(
-    pixels: &mut [u8],
-    bounds: (usize, usize),
-    upper_left: Complex<f64>,
-    lower_right: Complex<f64>,
-) {
-    assert_eq!(pixels.len(), bounds.0 * bounds.1);
-
-    for row in 0..bounds.1 {
-        for column in 0..bounds.0 {
-            let point = pixel_to_point(bounds, (column, row), upper_left, lower_right);
-            let pixel_index = row * bounds.0 + column;
-            pixels[pixel_index] = match escape_time(point, 255) {
-                None => 0,                        // Black color
-                Some(count) => 255 - count as u8, // The bigger count is, the darker the color
-            };
-        }
-    }
+/// Write `pixels` as a binary PGM (`P5`): the `image` crate isn't needed for
+/// this trivial a format, which makes it handy for piping into other tools
+/// and for very large images where PNG encoding is a bottleneck.
+fn write_pgm(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> Result<(), std::io::Error> {
+    let mut output = File::create(filename)?;
+
+    write!(output, "P5\n{} {}\n255\n", bounds.0, bounds.1)?;
+    output.write_all(&to_grayscale(pixels))?;
+
+    Ok(())
 }
 
-/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the
-/// file named `filename`
-fn write_image(
-    filename: &str,
-    pixels: &[u8],
-    bounds: (usize, usize),
-) -> Result<(), std::io::Error> {
-    let output = File::create(filename)?;
+/// Write `pixels` as a binary PPM (`P6`).
+fn write_ppm(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> Result<(), std::io::Error> {
+    let mut output = File::create(filename)?;
 
-    let encoder = PNGEncoder::new(output);
-    encoder.encode(pixels, bounds.0 as u32, bounds.1 as u32, ColorType::Gray(8))?;
+    write!(output, "P6\n{} {}\n255\n", bounds.0, bounds.1)?;
+    output.write_all(pixels)?;
 
     Ok(())
 }
+
+/// Collapse an RGB buffer down to one grayscale byte per pixel, using the
+/// standard luma weights.
+fn to_grayscale(rgb: &[u8]) -> Vec<u8> {
+    rgb.chunks_exact(3)
+        .map(|pixel| {
+            let [r, g, b] = [pixel[0] as f64, pixel[1] as f64, pixel[2] as f64];
+            (0.299 * r + 0.587 * g + 0.114 * b).round() as u8
+        })
+        .collect()
+}